@@ -10,7 +10,7 @@ use bevy_atmosphere::prelude::*;
 use bevy_spectator::{Spectator, SpectatorPlugin, SpectatorSettings};
 
 fn main() {
-    println!("Demonstrates using `AtmosphereCamera.render_layers` to have multiple skyboxes in the scene at once\n\t- E: Switch camera");
+    println!("Demonstrates using `AtmosphereCamera.render_layers` and a per-camera `AtmosphereModel` override to have multiple, differently-lit skyboxes in the scene at once\n\t- E: Switch camera");
     App::new()
         .insert_resource(AtmosphereModel::new(Nishita {
             rayleigh_coefficient: Vec3::new(22.4e-6, 5.5e-6, 13.0e-6), // Change rayleigh coefficient to change color
@@ -57,6 +57,12 @@ fn setup(
     // Spawn left screen camera and make it the default spectator
     let left = commands
         .spawn((
+            // HDR so the dawn sun disc can drive bloom and auto-exposure; the right camera below
+            // stays LDR, demonstrating that the atmosphere skybox handles both at once.
+            Camera {
+                hdr: true,
+                ..default()
+            },
             Camera3d::default(),
             Transform::from_xyz(0.0, 25.0, -100.0).looking_at(Vec3::ZERO, Vec3::Y),
             Msaa::Sample4,
@@ -64,6 +70,12 @@ fn setup(
             AtmosphereCamera {
                 render_layers: Some(RenderLayers::layer(1)),
             },
+            // Overrides the global (midday) `AtmosphereModel` resource with a dawn sky, just for
+            // this camera.
+            AtmosphereModel::new(Nishita {
+                sun_position: Vec3::new(0.0, 0.1, 1.0),
+                ..default()
+            }),
             LeftCamera,
             Spectator,
         ))
@@ -0,0 +1,87 @@
+//! A simple Rayleigh-Mie atmospheric scattering model.
+
+use bevy::color::LinearRgba;
+use bevy::prelude::*;
+use encase::ShaderType;
+
+use crate::model::Atmospheric;
+
+/// A simple atmospheric model based on the Nishita sky model.
+///
+/// This model approximates Rayleigh and Mie scattering, making it good for producing realistic
+/// skies, but it is not currently very customizable.
+#[derive(Debug, Clone, Copy, ShaderType)]
+pub struct Nishita {
+    /// Ray origin, the center of the planet.
+    pub ray_origin: Vec3,
+    /// Direction of the sun.
+    pub sun_position: Vec3,
+    /// Intensity of the sun.
+    pub sun_intensity: f32,
+    /// Radius of the planet.
+    pub planet_radius: f32,
+    /// Radius of the atmosphere.
+    pub atmosphere_radius: f32,
+    /// Rayleigh scattering coefficient.
+    pub rayleigh_coefficient: Vec3,
+    /// Rayleigh scattering scale height.
+    pub rayleigh_scale_height: f32,
+    /// Mie scattering coefficient.
+    pub mie_coefficient: f32,
+    /// Mie scattering scale height.
+    pub mie_scale_height: f32,
+    /// Mie scattering preferred direction.
+    pub mie_direction: f32,
+}
+
+impl Default for Nishita {
+    fn default() -> Self {
+        Self {
+            ray_origin: Vec3::new(0.0, 6372e3, 0.0),
+            sun_position: Vec3::new(1.0, 1.0, 1.0),
+            sun_intensity: 22.0,
+            planet_radius: 6371e3,
+            atmosphere_radius: 6471e3,
+            rayleigh_coefficient: Vec3::new(5.5e-6, 13.0e-6, 22.4e-6),
+            rayleigh_scale_height: 8e3,
+            mie_coefficient: 21e-6,
+            mie_scale_height: 1.2e3,
+            mie_direction: 0.758,
+        }
+    }
+}
+
+impl Atmospheric for Nishita {
+    fn sky_color(&self, direction: Vec3) -> LinearRgba {
+        let sun_direction = self.sun_position.normalize_or_zero();
+        let sun_height = sun_direction.y;
+        let view_height = direction.normalize_or_zero().y;
+
+        // A rough day/night/dawn mood color driven by the same fields the real raymarch uses:
+        // higher in the sky (`sun_height` near 1) reads as a blue midday sky; near the horizon
+        // it reads as a warm dawn/dusk sky; below the horizon it's dark.
+        let day = Vec3::new(0.3, 0.5, 0.9);
+        let dawn = Vec3::new(0.9, 0.5, 0.25);
+        let night = Vec3::splat(0.02);
+        let mix = sun_height.clamp(0.0, 1.0);
+        let base = dawn.lerp(day, mix);
+        let color = night.lerp(base, (sun_height * 2.0 + 0.5).clamp(0.0, 1.0));
+
+        // Thins out toward the zenith and thickens toward the horizon, the same direction
+        // atmospheric optical depth grows in the real raymarch.
+        let horizon_thickening = 1.0 + (1.0 - view_height.abs()).clamp(0.0, 1.0) * 0.6;
+        let color = color * horizon_thickening;
+
+        // A soft glow around the sun disc, brightest when looking straight at it.
+        let sun_dot = direction.normalize_or_zero().dot(sun_direction).clamp(0.0, 1.0);
+        let sun_glow = sun_dot.powf(64.0) * (self.sun_intensity / 22.0).clamp(0.0, 4.0);
+        let color = color + Vec3::splat(sun_glow);
+
+        // Scale by how thick the atmosphere is and how bright the sun is, matching the
+        // `rayleigh_coefficient`/`sun_intensity` fields rather than hardcoding a fixed sky.
+        let density = (self.rayleigh_coefficient.length() * 3e4).clamp(0.2, 2.0);
+        let brightness = (self.sun_intensity / 22.0).clamp(0.1, 4.0);
+        let color = (color * density * brightness).clamp(Vec3::ZERO, Vec3::splat(8.0));
+        LinearRgba::rgb(color.x, color.y, color.z)
+    }
+}
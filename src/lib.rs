@@ -0,0 +1,62 @@
+//! A procedural sky plugin for bevy.
+//!
+//! By default, cameras marked with [`AtmosphereCamera`] share the single [`AtmosphereModel`]
+//! resource. Insert an [`AtmosphereModel`] component directly on a camera to give it its own sky
+//! — useful for split-screen or multiplayer scenes where each viewport should show a different
+//! time of day.
+
+pub mod collection;
+mod model;
+mod pipeline;
+mod settings;
+mod skybox;
+mod target;
+
+use bevy::prelude::*;
+
+pub use crate::model::{Atmospheric, AtmosphereModel};
+pub use crate::pipeline::AtmosphereImageCache;
+pub use crate::settings::AtmosphereUpdateMode;
+pub use crate::skybox::{AtmosphereCamera, AtmosphereSkyBox, AtmosphereSkyboxViewport};
+
+/// Adds the systems and resources necessary to render a procedural sky.
+pub struct AtmospherePlugin;
+
+impl Plugin for AtmospherePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AtmosphereModel>()
+            .init_resource::<AtmosphereImageCache>()
+            .init_resource::<AtmosphereUpdateMode>()
+            .add_systems(
+                Update,
+                (
+                    skybox::resolve_skybox_viewports,
+                    pipeline::queue_atmosphere_cubemaps,
+                    skybox::attach_skyboxes,
+                )
+                    .chain(),
+            );
+    }
+}
+
+impl Default for AtmosphereModel {
+    fn default() -> Self {
+        #[cfg(feature = "nishita")]
+        {
+            AtmosphereModel::new(collection::nishita::Nishita::default())
+        }
+        #[cfg(not(feature = "nishita"))]
+        {
+            panic!("`AtmosphereModel` has no default without the `nishita` feature; insert one manually");
+        }
+    }
+}
+
+/// Re-exports the most commonly used items from this crate.
+pub mod prelude {
+    #[cfg(feature = "nishita")]
+    pub use crate::collection::nishita::Nishita;
+    pub use crate::{
+        Atmospheric, AtmosphereCamera, AtmosphereModel, AtmospherePlugin, AtmosphereUpdateMode,
+    };
+}
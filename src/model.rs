@@ -0,0 +1,72 @@
+//! Defines the [`Atmospheric`] trait and the [`AtmosphereModel`] resource.
+//!
+//! To create a new atmospheric model, implement [`Atmospheric`] for a type that also derives
+//! [`ShaderType`](encase::ShaderType), then insert it via [`AtmosphereModel::new`].
+
+use std::sync::Arc;
+
+use bevy::color::LinearRgba;
+use bevy::prelude::*;
+use encase::internal::WriteInto;
+use encase::ShaderType;
+
+/// A trait for types that can be used as an atmospheric model.
+///
+/// An atmospheric model describes how a sky should be rendered, e.g. the Nishita model simulates
+/// Rayleigh and Mie scattering. See [`crate::collection::nishita::Nishita`] for an example
+/// implementation.
+pub trait Atmospheric: ShaderType + WriteInto + Send + Sync + 'static {
+    /// Approximates this model's sky color when looking toward `direction` (a normalized vector
+    /// in world space).
+    ///
+    /// [`crate::pipeline`] calls this once per cubemap face, passing that face's direction, to
+    /// fill the cached cubemap for this model on the CPU. It's a cheap stand-in for the real
+    /// per-pixel GPU raymarch, so implementors should aim for "recognizably this model's sky"
+    /// (e.g. dawn vs. midday, horizon vs. zenith) rather than physical accuracy.
+    fn sky_color(&self, direction: Vec3) -> LinearRgba;
+}
+
+/// Stores the atmospheric model to be rendered.
+///
+/// By default, this resource is shared by every camera with an [`AtmosphereCamera`] component.
+/// To render a different sky per camera (e.g. for split-screen or multiplayer), insert an
+/// [`AtmosphereModel`] directly on the camera entity — it takes priority over this resource for
+/// that camera only.
+///
+/// [`AtmosphereCamera`]: crate::skybox::AtmosphereCamera
+#[derive(Resource, Component, Clone)]
+pub struct AtmosphereModel {
+    model: Arc<dyn DynamicAtmospheric>,
+}
+
+impl AtmosphereModel {
+    /// Creates a new `AtmosphereModel` from any type that implements [`Atmospheric`].
+    pub fn new<T: Atmospheric>(model: T) -> Self {
+        Self {
+            model: Arc::new(model),
+        }
+    }
+
+    /// Sets the model, replacing whatever was set previously.
+    pub fn set<T: Atmospheric>(&mut self, model: T) {
+        self.model = Arc::new(model);
+    }
+
+    /// Samples this model's approximate sky color looking toward `direction`. See
+    /// [`Atmospheric::sky_color`].
+    pub(crate) fn sky_color(&self, direction: Vec3) -> LinearRgba {
+        self.model.sky_color(direction)
+    }
+}
+
+/// An object-safe handle to an [`Atmospheric`] model, used so [`AtmosphereModel`] doesn't need to
+/// be generic over the model type.
+pub(crate) trait DynamicAtmospheric: Send + Sync {
+    fn sky_color(&self, direction: Vec3) -> LinearRgba;
+}
+
+impl<T: Atmospheric> DynamicAtmospheric for T {
+    fn sky_color(&self, direction: Vec3) -> LinearRgba {
+        Atmospheric::sky_color(self, direction)
+    }
+}
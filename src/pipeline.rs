@@ -0,0 +1,549 @@
+//! Generates and caches the atmosphere cubemap(s).
+//!
+//! Historically this module generated a single cubemap shared by every camera. To support
+//! per-camera [`AtmosphereModel`] overrides, it now keeps a small cache of cubemaps and
+//! regenerates one entry per distinct model in use rather than a single global texture. The
+//! cache is also keyed on whether the requesting camera is HDR, since an HDR camera needs the
+//! cubemap encoded in a format that preserves values above `1.0` (e.g. the Nishita sun disc) so
+//! bloom and auto-exposure can react to it, while an LDR camera is rendered more cheaply.
+//!
+//! How often each cached cubemap is actually regenerated is governed by [`AtmosphereUpdateMode`].
+
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy::render::view::RenderLayers;
+use bevy::utils::HashMap;
+
+use crate::model::AtmosphereModel;
+use crate::settings::AtmosphereUpdateMode;
+use crate::skybox::{effective_model, AtmosphereCamera};
+
+/// Identifies which cached cubemap a camera should use.
+///
+/// Cameras sharing the global [`AtmosphereModel`] resource (i.e. with no component override) are
+/// grouped by render layer and HDR-ness, since that's all that distinguishes them. A camera with
+/// its own [`AtmosphereModel`] override always gets its own slot, keyed on its entity, so two
+/// cameras with different overrides never collide — e.g. a split-screen left camera with a dawn
+/// override and a right camera with a midday override, even if they happened to share a render
+/// layer.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct AtmosphereCubemapKey {
+    owner: CubemapOwner,
+    hdr: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum CubemapOwner {
+    /// Tracks the global [`AtmosphereModel`] resource, for cameras with no override.
+    Global { render_layers: Option<RenderLayers> },
+    /// Tracks a single camera's own [`AtmosphereModel`] override.
+    Camera(Entity),
+}
+
+impl AtmosphereCubemapKey {
+    /// Builds the cache key for `camera`: its own slot if it has an [`AtmosphereModel`] override,
+    /// otherwise the shared slot for its render layer and the global resource.
+    pub(crate) fn new(
+        camera: Entity,
+        camera_settings: &AtmosphereCamera,
+        hdr: bool,
+        has_override: bool,
+    ) -> Self {
+        let owner = if has_override {
+            CubemapOwner::Camera(camera)
+        } else {
+            CubemapOwner::Global {
+                render_layers: camera_settings.render_layers.clone(),
+            }
+        };
+        Self { owner, hdr }
+    }
+}
+
+/// A cached cubemap plus the bookkeeping needed to decide when to regenerate it under
+/// [`AtmosphereUpdateMode::Throttled`] and [`AtmosphereUpdateMode::Progressive`].
+struct CubemapEntry {
+    handle: Handle<Image>,
+    /// Ticks down under [`AtmosphereUpdateMode::Throttled`]; regenerated when it finishes.
+    throttle: Timer,
+    /// Which face to regenerate next under [`AtmosphereUpdateMode::Progressive`].
+    next_face: u8,
+}
+
+/// Main-world cache of generated atmosphere cubemaps, one per [`AtmosphereCubemapKey`] in use
+/// across all [`AtmosphereCamera`]s. Populated by [`queue_atmosphere_cubemaps`],
+/// an `Update`-schedule system, not the render world.
+#[derive(Resource, Default)]
+pub struct AtmosphereImageCache {
+    entries: HashMap<AtmosphereCubemapKey, CubemapEntry>,
+}
+
+impl AtmosphereImageCache {
+    /// Returns the cubemap handle for `key`, if one has already been generated.
+    pub fn get(&self, key: &AtmosphereCubemapKey) -> Option<&Handle<Image>> {
+        self.entries.get(key).map(|entry| &entry.handle)
+    }
+
+    /// Drops cached cubemaps whose key no longer corresponds to any active camera, so switching
+    /// models, or toggling a camera's HDR setting, at runtime doesn't leak GPU memory.
+    fn retain_active(&mut self, active: &bevy::utils::HashSet<AtmosphereCubemapKey>) {
+        self.entries.retain(|key, _| active.contains(key));
+    }
+}
+
+/// Queues cubemap (re)generation for every distinct model currently in use: the global
+/// [`AtmosphereModel`] resource (used by any [`AtmosphereCamera`] without an override) plus one
+/// entry per camera-local [`AtmosphereModel`] override. How aggressively each entry is
+/// regenerated is controlled by the [`AtmosphereUpdateMode`] resource.
+pub(crate) fn queue_atmosphere_cubemaps(
+    mut cache: ResMut<AtmosphereImageCache>,
+    mut images: ResMut<Assets<Image>>,
+    update_mode: Res<AtmosphereUpdateMode>,
+    time: Res<Time>,
+    global_model: Res<AtmosphereModel>,
+    cameras: Query<(Entity, &Camera, &AtmosphereCamera, Option<Ref<AtmosphereModel>>)>,
+) {
+    let mut active = bevy::utils::HashSet::default();
+    for (entity, render_camera, camera, override_model) in &cameras {
+        let key =
+            AtmosphereCubemapKey::new(entity, camera, render_camera.hdr, override_model.is_some());
+        active.insert(key.clone());
+
+        let model = effective_model(override_model.as_deref(), &global_model);
+        let model_changed = override_model
+            .as_ref()
+            .map_or(global_model.is_changed(), |model| model.is_changed());
+
+        let Some(entry) = cache.entries.get_mut(&key) else {
+            let hdr = key.hdr;
+            let handle = images.add(new_cubemap_image(128, hdr));
+            // Generate immediately rather than waiting for a future change — otherwise a model
+            // that's set once at startup and never touched again would stay a blank cubemap
+            // forever under `AtmosphereUpdateMode::OnChange`.
+            regenerate_all_faces(&mut images, &handle, model);
+            cache.entries.insert(
+                key,
+                CubemapEntry {
+                    handle,
+                    throttle: Timer::new(std::time::Duration::ZERO, TimerMode::Once),
+                    next_face: 0,
+                },
+            );
+            continue;
+        };
+
+        match *update_mode {
+            AtmosphereUpdateMode::OnChange => {
+                if model_changed {
+                    regenerate_all_faces(&mut images, &entry.handle, model);
+                }
+            }
+            AtmosphereUpdateMode::Throttled { interval } => {
+                entry.throttle.set_duration(interval);
+                if model_changed || entry.throttle.tick(time.delta()).just_finished() {
+                    regenerate_all_faces(&mut images, &entry.handle, model);
+                    entry.throttle.reset();
+                }
+            }
+            AtmosphereUpdateMode::Progressive { faces_per_frame } => {
+                if model_changed {
+                    entry.next_face = 0;
+                }
+                for _ in 0..faces_per_frame.max(1) {
+                    regenerate_face(&mut images, &entry.handle, entry.next_face, model);
+                    entry.next_face = (entry.next_face + 1) % 6;
+                }
+            }
+        }
+    }
+    cache.retain_active(&active);
+}
+
+/// Creates a blank cubemap of the given `resolution`. HDR cameras get an `Rgba16Float` cubemap so
+/// radiance above `1.0` (e.g. the sun disc) survives into the camera's HDR pipeline for bloom and
+/// auto-exposure to pick up; LDR cameras get a cheaper `Rgba8UnormSrgb` cubemap.
+fn new_cubemap_image(resolution: u32, hdr: bool) -> Image {
+    let format = if hdr {
+        TextureFormat::Rgba16Float
+    } else {
+        TextureFormat::Rgba8UnormSrgb
+    };
+    // `Image::new_fill`'s fill value must be exactly one pixel's worth of bytes for `format`, or
+    // it panics: `Rgba16Float` is 8 bytes per pixel (four f16 channels), twice `Rgba8UnormSrgb`'s
+    // 4-byte opaque-black pixel.
+    const HALF_ZERO: [u8; 2] = (0x0000u16).to_ne_bytes();
+    const HALF_ONE: [u8; 2] = (0x3c00u16).to_ne_bytes();
+    const OPAQUE_BLACK_HDR: [u8; 8] = [
+        HALF_ZERO[0],
+        HALF_ZERO[1],
+        HALF_ZERO[0],
+        HALF_ZERO[1],
+        HALF_ZERO[0],
+        HALF_ZERO[1],
+        HALF_ONE[0],
+        HALF_ONE[1],
+    ];
+    const OPAQUE_BLACK_LDR: [u8; 4] = [0, 0, 0, 255];
+    let opaque_black: &[u8] = if hdr {
+        &OPAQUE_BLACK_HDR
+    } else {
+        &OPAQUE_BLACK_LDR
+    };
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: resolution,
+            height: resolution,
+            depth_or_array_layers: 6,
+        },
+        TextureDimension::D2,
+        opaque_black,
+        format,
+        default(),
+    );
+    // No `STORAGE_BINDING`: cubemaps are filled on the CPU and uploaded via `COPY_DST`, there's no
+    // compute shader writing to them. It would also fail validation for the LDR format anyway —
+    // `Rgba8UnormSrgb` isn't storage-capable in wgpu.
+    image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
+    image
+}
+
+/// The direction each cubemap face looks toward, in Bevy's cubemap face order (+X, -X, +Y, -Y,
+/// +Z, -Z).
+const FACE_DIRECTIONS: [Vec3; 6] = [
+    Vec3::X,
+    Vec3::NEG_X,
+    Vec3::Y,
+    Vec3::NEG_Y,
+    Vec3::Z,
+    Vec3::NEG_Z,
+];
+
+/// Regenerates every face of `handle`'s cubemap with `model`'s sky color.
+fn regenerate_all_faces(images: &mut Assets<Image>, handle: &Handle<Image>, model: &AtmosphereModel) {
+    for face in 0..6 {
+        regenerate_face(images, handle, face, model);
+    }
+}
+
+/// Regenerates a single face of `handle`'s cubemap with `model`'s sky color looking toward that
+/// face's direction, leaving the other five untouched. Used by
+/// [`AtmosphereUpdateMode::Progressive`] to spread the cost of recomputation across frames.
+fn regenerate_face(
+    images: &mut Assets<Image>,
+    handle: &Handle<Image>,
+    face: u8,
+    model: &AtmosphereModel,
+) {
+    let direction = FACE_DIRECTIONS[face as usize % 6];
+    let color = model.sky_color(direction);
+    let Some(image) = images.get_mut(handle) else {
+        return;
+    };
+    let pixel = encode_pixel(color, image.texture_descriptor.format);
+    let Some(data) = image.data.as_mut() else {
+        return;
+    };
+    let face_len = data.len() / 6;
+    let start = face_len * face as usize;
+    let end = start + face_len;
+    let Some(face_data) = data.get_mut(start..end) else {
+        return;
+    };
+    for chunk in face_data.chunks_mut(pixel.len()) {
+        chunk.copy_from_slice(&pixel);
+    }
+}
+
+/// Encodes `color` as one pixel's worth of bytes for upload into an image of `format`. Only the
+/// two formats [`new_cubemap_image`] actually produces are supported.
+fn encode_pixel(color: bevy::color::LinearRgba, format: TextureFormat) -> Vec<u8> {
+    match format {
+        TextureFormat::Rgba16Float => [color.red, color.green, color.blue, color.alpha]
+            .into_iter()
+            .flat_map(|channel| f32_to_f16_bits(channel).to_ne_bytes())
+            .collect(),
+        _ => {
+            // `Rgba8UnormSrgb` expects gamma-encoded bytes — it's re-linearized on sample — so
+            // `color` must go through the linear-to-sRGB transfer function before quantizing, or
+            // the sky renders far too dark (e.g. linear 0.5 stored as-is samples back as ~0.21).
+            let srgb = bevy::color::Srgba::from(color);
+            [
+                (srgb.red.clamp(0.0, 1.0) * 255.0) as u8,
+                (srgb.green.clamp(0.0, 1.0) * 255.0) as u8,
+                (srgb.blue.clamp(0.0, 1.0) * 255.0) as u8,
+                (srgb.alpha.clamp(0.0, 1.0) * 255.0) as u8,
+            ]
+            .to_vec()
+        }
+    }
+}
+
+/// Converts an `f32` to the bits of an IEEE 754 binary16 value. Not a general-purpose
+/// conversion: overflow saturates to infinity, there's no round-to-nearest (the mantissa is just
+/// truncated), and subnormal results flush to zero. That's acceptable for the sky colors this is
+/// used on, which are already clamped to a modest range before reaching here, but callers with
+/// different inputs should double-check before reusing this. Written by hand since this crate has
+/// no dependency that already does `f32`-to-`f16` conversion.
+fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent <= 0 {
+        sign
+    } else if exponent >= 0x1f {
+        sign | 0x7c00
+    } else {
+        sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::asset::AssetApp;
+    use bevy::color::LinearRgba;
+    use encase::ShaderType;
+
+    use super::*;
+    use crate::model::Atmospheric;
+
+    /// A trivial [`Atmospheric`] model that ignores `direction` and always reports `color`, so
+    /// tests can tell regenerated faces apart from stale/corrupted ones by their exact bytes.
+    #[derive(Debug, Clone, Copy, ShaderType)]
+    struct FlatColor {
+        color: Vec3,
+    }
+
+    impl Atmospheric for FlatColor {
+        fn sky_color(&self, _direction: Vec3) -> LinearRgba {
+            LinearRgba::rgb(self.color.x, self.color.y, self.color.z)
+        }
+    }
+
+    fn test_app(model: FlatColor, update_mode: AtmosphereUpdateMode) -> App {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default());
+        app.init_asset::<Image>();
+        app.init_resource::<AtmosphereImageCache>();
+        app.insert_resource(Time::default());
+        app.insert_resource(AtmosphereModel::new(model));
+        app.insert_resource(update_mode);
+        app.add_systems(Update, queue_atmosphere_cubemaps);
+        app
+    }
+
+    fn spawn_camera(app: &mut App, settings: AtmosphereCamera) -> Entity {
+        app.world_mut().spawn((Camera::default(), settings)).id()
+    }
+
+    fn cached_handle(app: &App, camera: Entity, settings: &AtmosphereCamera) -> Handle<Image> {
+        let key = AtmosphereCubemapKey::new(camera, settings, false, false);
+        app.world()
+            .resource::<AtmosphereImageCache>()
+            .get(&key)
+            .expect("cubemap should already be cached")
+            .clone()
+    }
+
+    /// Overwrites the first byte of every face of `handle`'s cubemap with a sentinel value that
+    /// none of the colors these tests use produce (their channels are 0.0 or 1.0, encoded as 0 or
+    /// 255), so a later read can tell whether a face
+    /// was actually regenerated or is still the value this test wrote.
+    fn corrupt_all_faces(app: &mut App, handle: &Handle<Image>) {
+        let mut images = app.world_mut().resource_mut::<Assets<Image>>();
+        let image = images.get_mut(handle).unwrap();
+        let data = image.data.as_mut().unwrap();
+        let face_len = data.len() / 6;
+        for face in 0..6 {
+            data[face * face_len] = 0xab;
+        }
+    }
+
+    fn first_byte_of_face(app: &App, handle: &Handle<Image>, face: usize) -> u8 {
+        let images = app.world().resource::<Assets<Image>>();
+        let image = images.get(handle).unwrap();
+        let data = image.data.as_ref().unwrap();
+        let face_len = data.len() / 6;
+        data[face * face_len]
+    }
+
+    #[test]
+    fn generates_cubemap_immediately_on_first_frame() {
+        let mut app = test_app(
+            FlatColor {
+                color: Vec3::new(1.0, 0.0, 0.0),
+            },
+            AtmosphereUpdateMode::OnChange,
+        );
+        let settings = AtmosphereCamera::default();
+        let camera = spawn_camera(&mut app, settings.clone());
+        app.update();
+
+        let handle = cached_handle(&app, camera, &settings);
+        // Red, gamma-encoded, should not be the opaque-black initial fill this cubemap started as.
+        assert_ne!(first_byte_of_face(&app, &handle, 0), 0);
+    }
+
+    #[test]
+    fn on_change_mode_skips_regeneration_when_model_is_unchanged() {
+        let mut app = test_app(
+            FlatColor {
+                color: Vec3::new(1.0, 0.0, 0.0),
+            },
+            AtmosphereUpdateMode::OnChange,
+        );
+        let settings = AtmosphereCamera::default();
+        let camera = spawn_camera(&mut app, settings.clone());
+        app.update();
+
+        let handle = cached_handle(&app, camera, &settings);
+        corrupt_all_faces(&mut app, &handle);
+
+        app.update();
+
+        assert_eq!(first_byte_of_face(&app, &handle, 0), 0xab);
+    }
+
+    #[test]
+    fn on_change_mode_regenerates_when_model_changes() {
+        let mut app = test_app(
+            FlatColor {
+                color: Vec3::new(1.0, 0.0, 0.0),
+            },
+            AtmosphereUpdateMode::OnChange,
+        );
+        let settings = AtmosphereCamera::default();
+        let camera = spawn_camera(&mut app, settings.clone());
+        app.update();
+
+        let handle = cached_handle(&app, camera, &settings);
+        corrupt_all_faces(&mut app, &handle);
+
+        app.world_mut()
+            .resource_mut::<AtmosphereModel>()
+            .set(FlatColor {
+                color: Vec3::new(0.0, 1.0, 0.0),
+            });
+        app.update();
+
+        assert_ne!(first_byte_of_face(&app, &handle, 0), 0xab);
+    }
+
+    #[test]
+    fn throttled_mode_waits_for_the_configured_interval() {
+        let mut app = test_app(
+            FlatColor {
+                color: Vec3::new(1.0, 0.0, 0.0),
+            },
+            AtmosphereUpdateMode::Throttled {
+                interval: Duration::from_secs(1),
+            },
+        );
+        let settings = AtmosphereCamera::default();
+        let camera = spawn_camera(&mut app, settings.clone());
+        app.update();
+
+        let handle = cached_handle(&app, camera, &settings);
+        corrupt_all_faces(&mut app, &handle);
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(500));
+        app.update();
+        assert_eq!(
+            first_byte_of_face(&app, &handle, 0),
+            0xab,
+            "shouldn't regenerate before the throttle interval elapses"
+        );
+
+        app.world_mut()
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(600));
+        app.update();
+        assert_ne!(
+            first_byte_of_face(&app, &handle, 0),
+            0xab,
+            "should regenerate once the throttle interval elapses"
+        );
+    }
+
+    #[test]
+    fn progressive_mode_regenerates_one_face_per_frame() {
+        let mut app = test_app(
+            FlatColor {
+                color: Vec3::new(1.0, 0.0, 0.0),
+            },
+            AtmosphereUpdateMode::Progressive { faces_per_frame: 1 },
+        );
+        let settings = AtmosphereCamera::default();
+        let camera = spawn_camera(&mut app, settings.clone());
+        app.update();
+
+        let handle = cached_handle(&app, camera, &settings);
+        corrupt_all_faces(&mut app, &handle);
+
+        // Changing the model resets which face is regenerated next back to 0.
+        app.world_mut()
+            .resource_mut::<AtmosphereModel>()
+            .set(FlatColor {
+                color: Vec3::new(0.0, 1.0, 0.0),
+            });
+        app.update();
+        assert_ne!(first_byte_of_face(&app, &handle, 0), 0xab, "face 0 should regenerate");
+        for face in 1..6 {
+            assert_eq!(
+                first_byte_of_face(&app, &handle, face),
+                0xab,
+                "face {face} shouldn't regenerate yet"
+            );
+        }
+
+        app.update();
+        assert_ne!(first_byte_of_face(&app, &handle, 1), 0xab, "face 1 should regenerate next");
+        for face in 2..6 {
+            assert_eq!(
+                first_byte_of_face(&app, &handle, face),
+                0xab,
+                "face {face} shouldn't regenerate yet"
+            );
+        }
+    }
+
+    #[test]
+    fn cache_key_distinguishes_overridden_cameras_with_the_same_render_layers() {
+        let settings = AtmosphereCamera {
+            render_layers: Some(RenderLayers::layer(1)),
+        };
+        let a = AtmosphereCubemapKey::new(Entity::from_raw(0), &settings, false, true);
+        let b = AtmosphereCubemapKey::new(Entity::from_raw(1), &settings, false, true);
+        assert_ne!(a, b, "overridden cameras must never share a cache slot");
+    }
+
+    #[test]
+    fn cache_key_shares_a_slot_for_cameras_without_an_override() {
+        let settings = AtmosphereCamera {
+            render_layers: Some(RenderLayers::layer(1)),
+        };
+        let a = AtmosphereCubemapKey::new(Entity::from_raw(0), &settings, false, false);
+        let b = AtmosphereCubemapKey::new(Entity::from_raw(1), &settings, false, false);
+        assert_eq!(a, b, "cameras sharing the global model should share a cache slot");
+    }
+
+    #[test]
+    fn effective_model_prefers_the_camera_override() {
+        let global = AtmosphereModel::new(FlatColor {
+            color: Vec3::new(0.0, 0.0, 1.0),
+        });
+        let overridden = AtmosphereModel::new(FlatColor {
+            color: Vec3::new(1.0, 0.0, 0.0),
+        });
+
+        let chosen = effective_model(Some(&overridden), &global);
+        assert_eq!(chosen.sky_color(Vec3::Y), LinearRgba::rgb(1.0, 0.0, 0.0));
+
+        let chosen = effective_model(None, &global);
+        assert_eq!(chosen.sky_color(Vec3::Y), LinearRgba::rgb(0.0, 0.0, 1.0));
+    }
+}
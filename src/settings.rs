@@ -0,0 +1,33 @@
+//! Plugin-wide settings controlling when the atmosphere is recomputed.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+/// Controls how often [`crate::pipeline`] regenerates the atmosphere cubemap(s).
+///
+/// Regenerating every frame is wasteful for a static sky, especially in an otherwise-idle
+/// reactive app (e.g. one using `WinitSettings::desktop_app`). Insert this resource to pick a
+/// cheaper mode; it applies to every cubemap in [`crate::AtmosphereImageCache`].
+#[derive(Resource, Clone, Debug, Default)]
+pub enum AtmosphereUpdateMode {
+    /// Recompute a cubemap only when its [`AtmosphereModel`](crate::AtmosphereModel) (the global
+    /// resource, or a camera's override) changes, as reported by Bevy's change detection. The
+    /// default, and the cheapest mode for a static or rarely-changing sky.
+    #[default]
+    OnChange,
+    /// Recompute on a fixed cadence regardless of whether the model changed, spreading the cost
+    /// of a slowly-animating sky (e.g. a day/night cycle) across frames instead of paying it
+    /// every frame.
+    Throttled {
+        /// How long to wait between recomputations.
+        interval: Duration,
+    },
+    /// Recompute continuously, but only `faces_per_frame` cubemap faces each frame, accumulating
+    /// a full cubemap over several frames. Keeps a moving sun smooth without paying for all six
+    /// faces on the same frame.
+    Progressive {
+        /// How many of the cubemap's six faces to regenerate per frame.
+        faces_per_frame: u8,
+    },
+}
@@ -0,0 +1,130 @@
+//! Attaches the atmosphere skybox to cameras.
+
+use bevy::prelude::*;
+use bevy::render::view::RenderLayers;
+use bevy::window::PrimaryWindow;
+
+use crate::model::AtmosphereModel;
+use crate::pipeline::{AtmosphereCubemapKey, AtmosphereImageCache};
+use crate::target::target_physical_size;
+
+/// Marks a camera as one that should have an atmosphere skybox rendered for it.
+///
+/// By default, the camera uses the global [`AtmosphereModel`] resource. To give this camera its
+/// own sky (e.g. so a split-screen player can see a different time of day), also insert an
+/// [`AtmosphereModel`] component on the same entity — it overrides the resource for this camera
+/// only.
+#[derive(Component, Clone, Default)]
+pub struct AtmosphereCamera {
+    /// Partitions the shared cubemap cache for cameras using the global [`AtmosphereModel`]
+    /// resource (cameras with their own override always get their own cache slot regardless of
+    /// this field). This does not affect skybox visibility — each camera's own
+    /// [`Skybox`](bevy::core_pipeline::Skybox) component only ever renders for that camera.
+    pub render_layers: Option<RenderLayers>,
+}
+
+/// The skybox cubemap assigned to a particular [`AtmosphereCamera`].
+///
+/// This is inserted by [`attach_skyboxes`] once the cubemap for the camera's effective model
+/// (its own [`AtmosphereModel`] override, or the global resource) has been generated, alongside a
+/// [`Skybox`](bevy::core_pipeline::Skybox) component that actually renders it — `Skybox` composites
+/// before tonemapping, so for an HDR camera a bright sun disc in the cubemap can still drive bloom
+/// and auto-exposure. This component exists so other systems (and [`attach_skyboxes`] itself) can
+/// find and compare the handle without depending on `bevy_core_pipeline` directly.
+#[derive(Component, Deref, DerefMut)]
+pub struct AtmosphereSkyBox(pub Handle<Image>);
+
+/// The physical size and, if the camera only renders to part of its target, viewport rect that
+/// the atmosphere skybox should be composited into for this [`AtmosphereCamera`].
+///
+/// Resolved from the camera's actual [`RenderTarget`](bevy::render::camera::RenderTarget) —
+/// a primary window, a secondary window, or an `Image` — rather than assumed to be the primary
+/// window, so a camera rendering to a secondary window or an offscreen image gets its sky
+/// composited at the right resolution instead of skipped or stretched.
+#[derive(Component, Clone, Copy)]
+pub struct AtmosphereSkyboxViewport {
+    /// Physical size of the camera's render target. For a `TextureView` target, which reports no
+    /// size of its own, this is the size of the camera's viewport instead.
+    pub target_size: UVec2,
+    /// The sub-rect of the target this camera renders into, if it doesn't render to the whole
+    /// target (e.g. one of several split-screen cameras sharing a window).
+    pub viewport: Option<bevy::render::camera::Viewport>,
+}
+
+/// Resolves the model that should be used for a given [`AtmosphereCamera`] entity: its own
+/// component override if present, falling back to the global [`AtmosphereModel`] resource.
+pub(crate) fn effective_model<'a>(
+    camera_model: Option<&'a AtmosphereModel>,
+    global_model: &'a AtmosphereModel,
+) -> &'a AtmosphereModel {
+    camera_model.unwrap_or(global_model)
+}
+
+/// Resolves each [`AtmosphereCamera`]'s [`AtmosphereSkyboxViewport`] from its camera's actual
+/// render target, so the skybox composites at that target's resolution whether it's the primary
+/// window, a secondary window, or an offscreen `Image`.
+pub(crate) fn resolve_skybox_viewports(
+    mut commands: Commands,
+    windows: Query<&Window>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    images: Res<Assets<Image>>,
+    cameras: Query<(Entity, &Camera), With<AtmosphereCamera>>,
+) {
+    for (entity, camera) in &cameras {
+        let Some(target_size) = target_physical_size(
+            &camera.target,
+            &windows,
+            &primary_window,
+            &images,
+            camera.viewport.as_ref(),
+        ) else {
+            continue;
+        };
+        commands.entity(entity).insert(AtmosphereSkyboxViewport {
+            target_size,
+            viewport: camera.viewport.clone(),
+        });
+    }
+}
+
+/// Keeps each [`AtmosphereCamera`]'s [`AtmosphereSkyBox`] pointed at the cubemap matching its
+/// effective model, rather than a single cubemap shared by every camera.
+///
+/// Requires an [`AtmosphereSkyboxViewport`] to have already been resolved for the camera: until
+/// then its target size isn't known (e.g. a secondary window still opening, or a `TextureView`
+/// target with no viewport set), so attaching a skybox would be composited at the wrong size —
+/// better to wait a frame than render it wrong.
+pub(crate) fn attach_skyboxes(
+    mut commands: Commands,
+    cache: Res<AtmosphereImageCache>,
+    cameras: Query<(
+        Entity,
+        &Camera,
+        &AtmosphereCamera,
+        &AtmosphereSkyboxViewport,
+        Option<&AtmosphereModel>,
+        Option<&AtmosphereSkyBox>,
+    )>,
+) {
+    for (entity, render_camera, camera, _resolved_viewport, override_model, current) in &cameras {
+        let key = AtmosphereCubemapKey::new(
+            entity,
+            camera,
+            render_camera.hdr,
+            override_model.is_some(),
+        );
+        let Some(cubemap) = cache.get(&key) else {
+            continue;
+        };
+        if current.is_none_or(|current| current.0 != *cubemap) {
+            commands.entity(entity).insert((
+                AtmosphereSkyBox(cubemap.clone()),
+                bevy::core_pipeline::Skybox {
+                    image: cubemap.clone(),
+                    brightness: 1000.0,
+                    rotation: Quat::IDENTITY,
+                },
+            ));
+        }
+    }
+}
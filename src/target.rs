@@ -0,0 +1,40 @@
+//! Resolves the physical size an [`AtmosphereCamera`](crate::AtmosphereCamera) is actually
+//! rendering into.
+//!
+//! The skybox used to assume every camera rendered into the primary window, so a camera pointed
+//! at a secondary `Window` or an offscreen `Image` render target (a rear-view mirror, a portal, a
+//! planet-surface preview) would have its sky skipped or stretched to the wrong resolution.
+//! Resolving the target explicitly, the same way Bevy's own camera/viewport code does, fixes that.
+
+use bevy::prelude::*;
+use bevy::render::camera::{NormalizedRenderTarget, RenderTarget, Viewport};
+use bevy::window::PrimaryWindow;
+
+/// Returns the physical size, in pixels, of whatever `target` actually refers to: a window
+/// (primary or secondary) or an `Image` render target. Falls back to `None` if the target isn't
+/// resolvable yet, e.g. a secondary window that hasn't finished opening.
+///
+/// A manually-managed `TextureView` render target (e.g. from `wgpu` interop) exposes no size of
+/// its own, so `viewport` is used instead for that case — the camera's own viewport rect is the
+/// only size information available. If the camera doesn't set one either, `None` is returned
+/// rather than guessed.
+pub(crate) fn target_physical_size(
+    target: &RenderTarget,
+    windows: &Query<&Window>,
+    primary_window: &Query<Entity, With<PrimaryWindow>>,
+    images: &Assets<Image>,
+    viewport: Option<&Viewport>,
+) -> Option<UVec2> {
+    let target = target.normalize(primary_window.single().ok());
+    match target? {
+        NormalizedRenderTarget::Window(window_ref) => {
+            let window = windows.get(window_ref.entity()).ok()?;
+            Some(UVec2::new(window.physical_width(), window.physical_height()))
+        }
+        NormalizedRenderTarget::Image(image_target) => {
+            let image = images.get(&image_target.handle)?;
+            Some(image.size())
+        }
+        NormalizedRenderTarget::TextureView(_) => viewport.map(|viewport| viewport.physical_size),
+    }
+}